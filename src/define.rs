@@ -1,3 +1,6 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
 use core::{
     fmt::{self, Debug, Formatter},
     ops::Range,
@@ -9,6 +12,63 @@ pub enum SGITarget<'a> {
     Targets(&'a [CPUTarget]),
 }
 
+impl SGITarget<'_> {
+    /// Builds the value to write to `ICC_SGI1R_EL1` to raise `intid` on this target.
+    ///
+    /// The affinity fields are taken from the first [CPUTarget] (all targets must
+    /// share the same `aff1`/`aff2`/`aff3` cluster, since the 16-bit TargetList can
+    /// only address `aff0` values 0-15), and the TargetList is built by OR-ing
+    /// `1 << aff0` for every target in that cluster.
+    pub fn icc_sgi1r(&self, intid: IntId) -> u64 {
+        assert!(SGI_RANGE.contains(&intid.to_u32()));
+        let sgi = intid.to_u32() as u64;
+        match self {
+            SGITarget::AllOther => {
+                const IRM: u64 = 1 << 40;
+                IRM | (sgi << 24)
+            }
+            SGITarget::Targets(targets) => {
+                let first = targets.first().expect("Targets must not be empty");
+                let mut target_list: u64 = 0;
+                for t in *targets {
+                    debug_assert_eq!(t.aff1, first.aff1);
+                    debug_assert_eq!(t.aff2, first.aff2);
+                    debug_assert_eq!(t.aff3, first.aff3);
+                    assert!(t.aff0 < 16, "TargetList only addresses aff0 values 0-15");
+                    target_list |= 1 << t.aff0;
+                }
+                target_list
+                    | (first.aff1 as u64) << 16
+                    | (sgi << 24)
+                    | (first.aff2 as u64) << 32
+                    | (first.aff3 as u64) << 48
+            }
+        }
+    }
+
+    /// Builds the value to write to `GICD_SGIR` to raise `intid` on this target.
+    ///
+    /// `GICD_SGIR`'s `CPUTargetList` is only 8 bits wide, so every [CPUTarget]
+    /// in `targets` must have `aff0` in 0-7 (GICv2 supports at most 8 PEs).
+    pub fn gicd_sgir(&self, intid: IntId) -> u32 {
+        assert!(SGI_RANGE.contains(&intid.to_u32()));
+        let sgi = intid.to_u32();
+        match self {
+            SGITarget::AllOther => {
+                const TARGET_LIST_FILTER_ALL_OTHER: u32 = 1 << 24;
+                TARGET_LIST_FILTER_ALL_OTHER | sgi
+            }
+            SGITarget::Targets(targets) => {
+                let mut target_list: u32 = 0;
+                for t in *targets {
+                    target_list |= t.cpu_target_list() as u32;
+                }
+                (target_list << 16) | sgi
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct CPUTarget {
     pub aff0: u8,
@@ -43,6 +103,10 @@ impl CPUTarget {
     }
 
     pub(crate) fn cpu_target_list(&self) -> u8 {
+        assert!(
+            self.aff0 < 8,
+            "GICD_SGIR's CPUTargetList only addresses aff0 values 0-7"
+        );
         1 << self.aff0
     }
 }
@@ -93,6 +157,17 @@ pub const SPECIAL_RANGE: Range<u32> = Range {
     end: 1024,
 };
 
+/// Interrupt ID 8192 and above are used for LPIs (Locality-specific Peripheral
+/// Interrupt).
+///
+/// LPIs are edge-triggered message-based interrupts, delivered via the
+/// Interrupt Translation Service (ITS), and are typically used for
+/// PCIe MSI/MSI-X.
+pub const LPI_RANGE: Range<u32> = Range {
+    start: 8192,
+    end: u32::MAX,
+};
+
 /// An interrupt ID.
 #[derive(Copy, Clone, Eq, Ord, PartialOrd, PartialEq)]
 pub struct IntId(u32);
@@ -102,7 +177,7 @@ impl IntId {
     /// # Safety
     /// `id` must be transformed into a valid [IntId]
     pub const unsafe fn raw(id: u32) -> Self {
-        assert!(id < SPECIAL_RANGE.end);
+        assert!(id < SPECIAL_RANGE.end || id >= LPI_RANGE.start);
         Self(id)
     }
 
@@ -124,6 +199,13 @@ impl IntId {
         Self(SPI_RANGE.start + spi)
     }
 
+    /// Returns the interrupt ID for the given Locality-specific Peripheral
+    /// Interrupt.
+    pub const fn lpi(lpi: u32) -> Self {
+        assert!(lpi < LPI_RANGE.end - LPI_RANGE.start);
+        Self(LPI_RANGE.start + lpi)
+    }
+
     /// Returns whether this interrupt ID is for a Software Generated Interrupt.
     pub fn is_sgi(&self) -> bool {
         SGI_RANGE.contains(&self.0)
@@ -134,19 +216,68 @@ impl IntId {
         self.0 < SPI_RANGE.start
     }
 
+    /// Returns whether this interrupt ID is for a Locality-specific Peripheral
+    /// Interrupt.
+    pub fn is_lpi(&self) -> bool {
+        self.0 >= LPI_RANGE.start
+    }
+
+    /// Returns whether this interrupt ID is for a Private Peripheral Interrupt.
+    pub fn is_ppi(&self) -> bool {
+        PPI_RANGE.contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is for a Shared Peripheral Interrupt.
+    pub fn is_spi(&self) -> bool {
+        SPI_RANGE.contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is one of the special reserved IDs.
+    pub fn is_special(&self) -> bool {
+        SPECIAL_RANGE.contains(&self.0)
+    }
+
+    /// Classifies this interrupt ID, returning its class together with its
+    /// index relative to the start of that class's range.
+    pub fn kind(&self) -> IntKind {
+        match self.0 {
+            n if SGI_RANGE.contains(&n) => IntKind::Sgi(n - SGI_RANGE.start),
+            n if PPI_RANGE.contains(&n) => IntKind::Ppi(n - PPI_RANGE.start),
+            n if SPI_RANGE.contains(&n) => IntKind::Spi(n - SPI_RANGE.start),
+            n if n >= LPI_RANGE.start => IntKind::Lpi(n - LPI_RANGE.start),
+            n => IntKind::Special(n - SPECIAL_RANGE.start),
+        }
+    }
+
     pub fn to_u32(&self) -> u32 {
         self.0
     }
 }
 
+/// The class of interrupt an [IntId] belongs to, together with its index
+/// relative to the start of that class's range.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IntKind {
+    /// Software Generated Interrupt, with its index within [SGI_RANGE].
+    Sgi(u32),
+    /// Private Peripheral Interrupt, with its index within [PPI_RANGE].
+    Ppi(u32),
+    /// Shared Peripheral Interrupt, with its index within [SPI_RANGE].
+    Spi(u32),
+    /// Locality-specific Peripheral Interrupt, with its index within [LPI_RANGE].
+    Lpi(u32),
+    /// One of the special reserved IDs, with its index within [SPECIAL_RANGE].
+    Special(u32),
+}
+
 impl Debug for IntId {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self.0 {
-            0..16 => write!(f, "SGI {}", self.0 - SGI_RANGE.start),
-            16..32 => write!(f, "PPI {}", self.0 - PPI_RANGE.start),
-            32..1020 => write!(f, "SPI {}", self.0 - SPI_RANGE.start),
-            1020..1024 => write!(f, "Special IntId{}", self.0),
-            _ => write!(f, "Invalid IntId{}", self.0),
+        match self.kind() {
+            IntKind::Sgi(n) => write!(f, "SGI {n}"),
+            IntKind::Ppi(n) => write!(f, "PPI {n}"),
+            IntKind::Spi(n) => write!(f, "SPI {n}"),
+            IntKind::Lpi(n) => write!(f, "LPI {n}"),
+            IntKind::Special(_) => write!(f, "Special IntId{}", self.0),
         }
     }
 }
@@ -166,6 +297,42 @@ pub enum Trigger {
     Level,
 }
 
+/// The security group an interrupt is assigned to.
+///
+/// Group 0 interrupts are signalled through FIQ and are reserved for secure
+/// world use; Group 1 interrupts are signalled through IRQ and are split into
+/// a secure and a non-secure variant.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Group {
+    /// Group 0, routed to FIQ.
+    Group0,
+    /// Group 1 Non-secure, routed to IRQ.
+    Group1NonSecure,
+    /// Group 1 Secure, routed to IRQ.
+    Group1Secure,
+}
+
+impl Group {
+    /// Decodes a `Group` from the `GICD_IGROUPR`/`GICR_IGROUPR0` bit and the
+    /// `GICD_IGRPMODR`/`GICR_IGRPMODR0` bit for a given interrupt.
+    pub fn from_bits(group_bit: bool, grpmod_bit: bool) -> Self {
+        match (group_bit, grpmod_bit) {
+            (false, _) => Group::Group0,
+            (true, false) => Group::Group1NonSecure,
+            (true, true) => Group::Group1Secure,
+        }
+    }
+
+    /// Encodes this `Group` as `(IGROUP bit, IGRPMOD bit)`.
+    pub fn to_bits(self) -> (bool, bool) {
+        match self {
+            Group::Group0 => (false, false),
+            Group::Group1NonSecure => (true, false),
+            Group::Group1Secure => (true, true),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum GicError {
     Notimplemented,
@@ -174,6 +341,49 @@ pub enum GicError {
 
 pub type GicResult<T = ()> = core::result::Result<T, GicError>;
 
+/// Per-interrupt state captured by [GicGeneric::save_state], shared by both
+/// SPIs (distributor-wide) and SGIs/PPIs (per-redistributor on GICv3).
+#[derive(Debug, Clone)]
+pub struct IrqState {
+    pub intid: IntId,
+    pub enabled: bool,
+    pub priority: usize,
+    pub trigger: Trigger,
+    pub group: Group,
+}
+
+/// Per-redistributor (i.e. per-CPU) SGI/PPI state, plus the CPU interface
+/// control registers for that core.
+#[derive(Debug, Clone)]
+pub struct RedistributorState {
+    pub private: Vec<IrqState>,
+    pub priority_mask: u8,
+    pub ctlr: u32,
+}
+
+/// A snapshot of distributor, redistributor and CPU-interface state, as
+/// captured by [GicGeneric::save_state] and reapplied by
+/// [GicGeneric::restore_state].
+///
+/// Distributor and redistributor state is lost whenever their power domain
+/// is turned off, so this must be saved before a deep-idle or suspend
+/// transition and restored afterwards.
+#[derive(Debug, Clone)]
+pub struct GicState {
+    pub spis: Vec<IrqState>,
+    pub redistributors: Vec<RedistributorState>,
+}
+
+/// Common operations exposed by a GIC driver, independent of GIC version.
+///
+/// This trait only describes the contract; the register layout differs
+/// between GICv2 (memory-mapped `GICD`/`GICC`) and GICv3 (memory-mapped
+/// `GICD`/`GICR` plus `ICC_*_EL1` system registers), so concrete
+/// implementations live in this crate's version-specific driver modules, not
+/// here. Free functions and methods such as [SGITarget::icc_sgi1r]/
+/// [SGITarget::gicd_sgir] and [Group::to_bits]/[Group::from_bits] encode the
+/// version-specific wire formats those drivers need, so the bit-packing only
+/// has to be written once.
 pub trait GicGeneric {
     fn get_and_acknowledge_interrupt(&self) -> Option<IntId>;
     fn end_interrupt(&self, intid: IntId);
@@ -183,5 +393,173 @@ pub trait GicGeneric {
     fn set_priority(&mut self, intid: IntId, priority: usize);
     fn set_trigger(&mut self, intid: IntId, trigger: Trigger);
     fn set_bind_cpu(&mut self, intid: IntId, cpu_list: &[CPUTarget]);
+
+    /// Performs this core's one-time CPU interface setup.
+    ///
+    /// Defaults every private interrupt (SGI/PPI) to [Group::Group1NonSecure]
+    /// and enables Group 1 signaling in the CPU interface control register
+    /// (`ICC_IGRPEN1_EL1`/`GICC_CTLR`), so a kernel that does not otherwise
+    /// manage groups still receives interrupts through IRQ.
     fn current_cpu_setup(&self);
+
+    /// Sends a Software Generated Interrupt to the given `target`.
+    ///
+    /// `intid` must be in [SGI_RANGE]. Implementations write
+    /// [SGITarget::icc_sgi1r] to `ICC_SGI1R_EL1` on GICv3, or
+    /// [SGITarget::gicd_sgir] to `GICD_SGIR` on GICv2.
+    fn send_sgi(&self, intid: IntId, target: SGITarget);
+
+    /// Assigns `intid` to the given security `group`.
+    ///
+    /// Implementations write the `(IGROUP bit, IGRPMOD bit)` pair returned by
+    /// [Group::to_bits] to `GICD_IGROUPR`/`GICD_IGRPMODR` (or the
+    /// redistributor equivalents for private interrupts).
+    fn set_group(&mut self, intid: IntId, group: Group);
+
+    /// Returns the security group `intid` is currently assigned to.
+    ///
+    /// Implementations read the IGROUP/IGRPMOD bits and decode them with
+    /// [Group::from_bits].
+    fn group(&self, intid: IntId) -> Group;
+
+    /// Masks all interrupts whose priority is numerically greater than or
+    /// equal to `mask` (writes `ICC_PMR_EL1` / `GICC_PMR`). Lower numeric
+    /// values are higher priority, so `mask = 0` blocks every interrupt and
+    /// `mask = 0xff` blocks none.
+    fn set_priority_mask(&self, mask: u8);
+
+    /// Splits the 8-bit priority of interrupts in `group` into a group-priority
+    /// field and a sub-priority field at `point` (writes `ICC_BPR0/1_EL1` /
+    /// `GICC_BPR`), so that only the group-priority field participates in
+    /// preemption.
+    ///
+    /// `point` is the number of low-order priority bits treated as
+    /// sub-priority and must be in `0..=7`; implementations should clamp to
+    /// the number of priority bits the CPU interface actually implements.
+    fn set_binary_point(&self, group: Group, point: u8);
+
+    /// Returns the priority of the interrupt currently being handled by this
+    /// CPU interface (reads `ICC_RPR_EL1`). As with [Self::set_priority_mask],
+    /// lower numeric values are higher priority.
+    fn running_priority(&self) -> u8;
+
+    /// Captures distributor, redistributor and CPU-interface state so it can
+    /// be reapplied with [Self::restore_state] after a power transition that
+    /// loses it.
+    fn save_state(&self) -> GicState;
+
+    /// Reapplies a snapshot previously captured with [Self::save_state].
+    ///
+    /// Distributor configuration is re-applied before the CPU interface is
+    /// re-enabled. Each write that triggers a register-write-pending cycle
+    /// (e.g. `GICD_CTLR`/`GICR_CTLR`) must be followed by spin-polling the
+    /// corresponding `RWP` bit until it clears, the same wait-then-timeout
+    /// pattern this crate's ITS command-queue polling uses, returning
+    /// [GicError::Timeout] if `RWP` never clears.
+    fn restore_state(&mut self, state: &GicState) -> GicResult;
+
+    /// Returns whether `intid` is currently pending (`GICD_ISPENDR` /
+    /// redistributor equivalent for private interrupts).
+    fn is_pending(&self, intid: IntId) -> bool;
+
+    /// Sets or clears the pending state of `intid` (`GICD_ISPENDR`/
+    /// `GICD_ICPENDR`, or the redistributor equivalents for private
+    /// interrupts).
+    fn set_pending(&mut self, intid: IntId, pending: bool);
+
+    /// Returns whether `intid` is currently active (`GICD_ISACTIVER` /
+    /// redistributor equivalent for private interrupts).
+    fn is_active(&self, intid: IntId) -> bool;
+
+    /// Clears the active state of `intid` (`GICD_ICACTIVER` / redistributor
+    /// equivalent for private interrupts), for tearing down an interrupt
+    /// that fired but was never acknowledged.
+    fn clear_active(&mut self, intid: IntId);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icc_sgi1r_all_other() {
+        let value = SGITarget::AllOther.icc_sgi1r(IntId::sgi(3));
+        assert_eq!(value, (1 << 40) | (3 << 24));
+    }
+
+    #[test]
+    fn icc_sgi1r_targets_packs_affinity_and_target_list() {
+        let targets = [
+            CPUTarget {
+                aff0: 0,
+                aff1: 1,
+                aff2: 2,
+                aff3: 3,
+            },
+            CPUTarget {
+                aff0: 2,
+                aff1: 1,
+                aff2: 2,
+                aff3: 3,
+            },
+        ];
+        let value = SGITarget::Targets(&targets).icc_sgi1r(IntId::sgi(5));
+        let expected_target_list = (1 << 0) | (1 << 2);
+        let expected = expected_target_list
+            | (1u64 << 16) // aff1
+            | (5u64 << 24) // intid
+            | (2u64 << 32) // aff2
+            | (3u64 << 48); // aff3
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn gicd_sgir_all_other() {
+        let value = SGITarget::AllOther.gicd_sgir(IntId::sgi(7));
+        assert_eq!(value, (1 << 24) | 7);
+    }
+
+    #[test]
+    fn gicd_sgir_targets_packs_target_list() {
+        let targets = [CPUTarget::CORE0];
+        let value = SGITarget::Targets(&targets).gicd_sgir(IntId::sgi(2));
+        assert_eq!(value, (1 << 16) | 2);
+    }
+
+    #[test]
+    fn group_bits_round_trip() {
+        for group in [Group::Group0, Group::Group1NonSecure, Group::Group1Secure] {
+            let (group_bit, grpmod_bit) = group.to_bits();
+            assert_eq!(Group::from_bits(group_bit, grpmod_bit), group);
+        }
+    }
+
+    #[test]
+    fn group_from_bits_matches_spec_encoding() {
+        assert_eq!(Group::from_bits(false, false), Group::Group0);
+        assert_eq!(Group::from_bits(false, true), Group::Group0);
+        assert_eq!(Group::from_bits(true, false), Group::Group1NonSecure);
+        assert_eq!(Group::from_bits(true, true), Group::Group1Secure);
+    }
+
+    #[test]
+    fn kind_reports_index_relative_to_its_range_start() {
+        assert_eq!(IntId::sgi(3).kind(), IntKind::Sgi(3));
+        assert_eq!(IntId::ppi(0).kind(), IntKind::Ppi(0));
+        assert_eq!(IntId::spi(100).kind(), IntKind::Spi(100));
+        assert_eq!(IntId::lpi(5).kind(), IntKind::Lpi(5));
+    }
+
+    #[test]
+    fn kind_covers_range_boundaries() {
+        assert_eq!(IntId::sgi(SGI_RANGE.end - 1).kind(), IntKind::Sgi(15));
+        assert_eq!(
+            IntId::ppi(PPI_RANGE.end - PPI_RANGE.start - 1).kind(),
+            IntKind::Ppi(15)
+        );
+        assert_eq!(
+            unsafe { IntId::raw(SPECIAL_RANGE.start) }.kind(),
+            IntKind::Special(0)
+        );
+    }
 }
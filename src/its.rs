@@ -0,0 +1,415 @@
+//! Interrupt Translation Service (ITS) support for GICv3 LPIs.
+//!
+//! The ITS translates a (DeviceID, EventID) pair into an LPI targeting a
+//! specific redistributor, which is how PCIe MSI/MSI-X interrupts are
+//! delivered on GICv3 systems. See the GICv3 architecture specification,
+//! chapter 5, for the register and command layout this module implements.
+
+use crate::define::{GicError, GicResult, IntId};
+
+/// Number of bytes in an ITS command queue entry.
+const COMMAND_SIZE: usize = 32;
+
+/// An ITS command, encoded as the four 64-bit words written to the command
+/// queue.
+#[repr(C, align(32))]
+#[derive(Clone, Copy)]
+struct Command([u64; 4]);
+
+impl Command {
+    const fn new(id: u8) -> Self {
+        Self([id as u64, 0, 0, 0])
+    }
+}
+
+/// Number of bytes in a `GITS_CBASER` page, per the GICv3 spec.
+const CBASER_PAGE_SIZE: usize = 4096;
+
+/// Builds the value to write to `GITS_CBASER` for a `len`-command queue at
+/// `cmd_queue`: the physical address, the `Size` field (number of
+/// `CBASER_PAGE_SIZE` pages the queue spans, minus one), and the `Valid` bit,
+/// without which the ITS never consumes entries from the queue.
+fn cbaser_value(cmd_queue: *mut Command, len: usize) -> u64 {
+    const VALID: u64 = 1 << 63;
+    let bytes = len * COMMAND_SIZE;
+    let pages = bytes.div_ceil(CBASER_PAGE_SIZE).max(1);
+    assert!(
+        pages <= 256,
+        "command queue does not fit GITS_CBASER's 8-bit Size field"
+    );
+    let size_field = (pages - 1) as u64;
+    (cmd_queue as u64) | size_field | VALID
+}
+
+/// Builds the `MAPD` ITT size field: the number of bits needed to represent
+/// `num_events` EventIDs, minus one. The ITS always needs at least one
+/// EventID bit (to distinguish EventID 0 from "no entry"), so `num_events`
+/// of 0 or 1 both encode to the minimum field value of 0.
+fn itt_size_field(num_events: u32) -> u32 {
+    let bits = num_events.next_power_of_two().trailing_zeros().max(1);
+    bits - 1
+}
+
+/// Builds the `GICR_PROPBASER` `IDbits` field: the number of bits needed to
+/// represent the highest supported LPI INTID, minus one. The GICv3
+/// architecture requires every redistributor to support at least 14 ID bits
+/// (field value 13) regardless of how few LPIs are actually configured, so
+/// `num_lpis` below that floor still encodes to the minimum.
+fn propbaser_id_bits_field(num_lpis: u32) -> u32 {
+    const MIN_ID_BITS: u32 = 14;
+    let highest_id = crate::define::LPI_RANGE.start + num_lpis.max(1) - 1;
+    let bits = (u32::BITS - highest_id.leading_zeros()).max(MIN_ID_BITS);
+    bits - 1
+}
+
+/// Number of `GITS_BASERn` registers implemented, GICv3 spec section 8.24.
+const NUM_BASER_REGS: usize = 8;
+
+/// `GITS_BASERn.Type` field values identifying what a table holds, GICv3
+/// spec table 8-16 (subset this driver cares about).
+mod baser_type {
+    pub const DEVICES: u64 = 1;
+}
+
+/// Builds the value to write to a `GITS_BASERn` for a `size`-byte table at
+/// `table`, tagged with `ty` so the ITS knows what the table holds. Mirrors
+/// [cbaser_value]'s `Size`/`Valid` encoding and carries `ty` in the `Type`
+/// field; `GITS_BASERn.Type` is read-only, so writing this value does not
+/// change it (it must already match `ty`).
+fn baser_value(table: *mut u8, size: usize, ty: u64) -> u64 {
+    const VALID: u64 = 1 << 63;
+    const TYPE_SHIFT: u64 = 56;
+    const PAGE_SIZE: usize = 4096;
+    let pages = size.div_ceil(PAGE_SIZE).max(1);
+    assert!(
+        pages <= 256,
+        "table does not fit GITS_BASERn's 8-bit Size field"
+    );
+    let size_field = (pages - 1) as u64;
+    (table as u64) | size_field | (ty << TYPE_SHIFT) | VALID
+}
+
+/// Command opcodes, GICv3 spec table 5-1.
+mod opcode {
+    pub const MAPD: u8 = 0x08;
+    pub const MAPC: u8 = 0x09;
+    pub const MAPTI: u8 = 0x0a;
+    pub const INV: u8 = 0x0c;
+    pub const DISCARD: u8 = 0x0f;
+}
+
+/// A collection ID, grouping LPIs that are routed to the same redistributor.
+pub type CollectionId = u16;
+
+/// A PCI-style device identifier, as used by `MAPD`/`MAPTI`.
+pub type DeviceId = u32;
+
+/// An event identifier within a device, as used by `MAPTI`.
+pub type EventId = u32;
+
+/// Driver for the GICv3 Interrupt Translation Service.
+///
+/// Drives the ITS command queue, programs a redistributor's LPI
+/// configuration/pending tables via [Self::configure_lpi_tables], and
+/// exposes the subset of ITS commands needed to route message-signalled
+/// interrupts to LPIs. The command queue and the tables are memory supplied
+/// by the caller: `Its` allocates none of them, and it keeps no reference to
+/// the LPI tables once they are programmed.
+pub struct Its {
+    gits_base: *mut u8,
+    cmd_queue: *mut Command,
+    cmd_queue_len: usize,
+    write_idx: usize,
+}
+
+impl Its {
+    /// Creates a new `Its` driving the ITS at `gits_base`, using
+    /// `cmd_queue` (a `GITS_CBASER`-aligned buffer of `len` commands) as the
+    /// command queue, then sets `GITS_CTLR.Enabled` so the ITS actually
+    /// starts consuming commands from it.
+    ///
+    /// # Safety
+    /// `gits_base` must be the base address of a GICv3 ITS register frame,
+    /// and `cmd_queue` must point to `len * 32` bytes of memory suitable for
+    /// the ITS to read as its command queue, both valid for as long as this
+    /// `Its` is in use.
+    pub unsafe fn new(gits_base: *mut u8, cmd_queue: *mut u8, len: usize) -> Self {
+        let its = Self {
+            gits_base,
+            cmd_queue: cmd_queue as *mut Command,
+            cmd_queue_len: len,
+            write_idx: 0,
+        };
+        unsafe {
+            its.write_reg(reg::GITS_CBASER, cbaser_value(its.cmd_queue, len));
+            its.write_reg(reg::GITS_CWRITER, 0);
+            // GITS_CTLR is a 32-bit register; a 64-bit write would also hit
+            // the read-only GITS_IIDR immediately above it.
+            (gits_base.add(reg::GITS_CTLR) as *mut u32).write_volatile(reg::GITS_CTLR_ENABLED);
+        }
+        its
+    }
+
+    /// Programs a redistributor's `GICR_PROPBASER`/`GICR_PENDBASER` with the
+    /// LPI configuration table (`prop_table`, one byte per LPI) and pending
+    /// table (`pend_table`), covering `num_lpis` LPIs, then sets
+    /// `GICR_CTLR.EnableLPIs` so the redistributor actually starts accepting
+    /// them (tables alone are inert until this bit is set).
+    ///
+    /// # Safety
+    /// `rdist_base` must be the base address of a GICv3 redistributor
+    /// RD_base register frame; `prop_table` and `pend_table` must point to
+    /// memory sized and aligned per the GICv3 spec (`2^(ID bits) - 8192`
+    /// bytes, 4KiB-aligned, and 64KiB for the pending table respectively)
+    /// and must outlive the redistributor's use of them.
+    pub unsafe fn configure_lpi_tables(
+        &self,
+        rdist_base: *mut u8,
+        prop_table: *mut u8,
+        pend_table: *mut u8,
+        num_lpis: u32,
+    ) -> GicResult {
+        unsafe {
+            let propbaser = (prop_table as u64) | propbaser_id_bits_field(num_lpis) as u64;
+            (rdist_base.add(reg::GICR_PROPBASER) as *mut u64).write_volatile(propbaser);
+            (rdist_base.add(reg::GICR_PENDBASER) as *mut u64).write_volatile(pend_table as u64);
+
+            let ctlr = rdist_base.add(reg::GICR_CTLR) as *mut u32;
+            ctlr.write_volatile(ctlr.read_volatile() | reg::GICR_CTLR_ENABLE_LPIS);
+            Self::wait_for_rwp(rdist_base)
+        }
+    }
+
+    /// Programs the device table backing `MAPD`/`MAPTI` with a `size`-byte
+    /// table at `table`, by scanning `GITS_BASERn` for the register the ITS
+    /// reports as holding devices (`Type == Devices`) and writing `table`'s
+    /// address, size and `Valid` bit there. Without this, `MAPD` has nowhere
+    /// to record the device it maps.
+    ///
+    /// # Safety
+    /// `table` must point to `size` bytes of memory sized and aligned per
+    /// the GICv3 spec for however many devices this ITS will map, valid for
+    /// as long as this `Its` is in use.
+    pub unsafe fn configure_device_table(&self, table: *mut u8, size: usize) {
+        const TYPE_SHIFT: u64 = 56;
+        const TYPE_MASK: u64 = 0b111;
+        for n in 0..NUM_BASER_REGS {
+            let offset = reg::GITS_BASER0 + n * 8;
+            let ty = unsafe { self.read_reg(offset) } >> TYPE_SHIFT & TYPE_MASK;
+            if ty == baser_type::DEVICES {
+                unsafe { self.write_reg(offset, baser_value(table, size, baser_type::DEVICES)) };
+                return;
+            }
+        }
+    }
+
+    /// Binds `collection` to the redistributor at `rdist_base`, so LPIs
+    /// mapped to `collection` via `MAPTI` are delivered to that core
+    /// (`MAPC`). Must be done before any `MAPTI` referencing `collection`.
+    pub fn map_collection(&mut self, collection: CollectionId, rdist_base: *mut u8) -> GicResult {
+        const VALID: u64 = 1 << 63;
+        let mut cmd = Command::new(opcode::MAPC);
+        cmd.0[2] = (collection as u64) | (rdist_base as u64 >> 16) << 16 | VALID;
+        self.push(cmd);
+        self.wait_for_completion()
+    }
+
+    /// Spin-polls `GICR_CTLR.RWP` until the redistributor has consumed the
+    /// write that precedes this call, or returns [GicError::Timeout] if it
+    /// never clears; the same wait-then-timeout pattern
+    /// [Self::wait_for_completion] uses for the ITS command queue.
+    ///
+    /// # Safety
+    /// `rdist_base` must be the base address of a GICv3 redistributor
+    /// RD_base register frame.
+    unsafe fn wait_for_rwp(rdist_base: *mut u8) -> GicResult {
+        let ctlr = rdist_base.add(reg::GICR_CTLR) as *const u32;
+        for _ in 0..1_000_000 {
+            if unsafe { ctlr.read_volatile() } & reg::GICR_CTLR_RWP == 0 {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(GicError::Timeout)
+    }
+
+    unsafe fn write_reg(&self, offset: usize, value: u64) {
+        unsafe { (self.gits_base.add(offset) as *mut u64).write_volatile(value) }
+    }
+
+    unsafe fn read_reg(&self, offset: usize) -> u64 {
+        unsafe { (self.gits_base.add(offset) as *const u64).read_volatile() }
+    }
+
+    /// Appends `cmd` to the command queue and updates `GITS_CWRITER`.
+    fn push(&mut self, cmd: Command) {
+        unsafe {
+            self.cmd_queue.add(self.write_idx).write_volatile(cmd);
+        }
+        self.write_idx = (self.write_idx + 1) % self.cmd_queue_len;
+        let writer = (self.write_idx * COMMAND_SIZE) as u64;
+        unsafe { self.write_reg(reg::GITS_CWRITER, writer) };
+    }
+
+    /// Maps `device` into the ITS device table with `num_events` usable
+    /// EventIDs, backed by the Interrupt Translation Table at `itt_addr`
+    /// (`MAPD`).
+    ///
+    /// # Safety
+    /// `itt_addr` must be 256-byte aligned and point to memory sized per the
+    /// GICv3 spec for `num_events` entries, valid for as long as `device`
+    /// stays mapped.
+    pub unsafe fn map_device(
+        &mut self,
+        device: DeviceId,
+        itt_addr: *mut u8,
+        num_events: u32,
+    ) -> GicResult {
+        const VALID: u64 = 1 << 63;
+        let mut cmd = Command::new(opcode::MAPD);
+        cmd.0[0] |= (device as u64) << 32;
+        cmd.0[1] = itt_size_field(num_events) as u64;
+        cmd.0[2] = (itt_addr as u64) | VALID;
+        self.push(cmd);
+        self.wait_for_completion()
+    }
+
+    /// Maps `(device, event)` to `intid` on `collection` (`MAPTI`).
+    pub fn map_interrupt(
+        &mut self,
+        device: DeviceId,
+        event: EventId,
+        intid: IntId,
+        collection: CollectionId,
+    ) -> GicResult {
+        let mut cmd = Command::new(opcode::MAPTI);
+        cmd.0[0] |= (device as u64) << 32;
+        cmd.0[1] = event as u64 | (intid.to_u32() as u64) << 32;
+        cmd.0[2] = collection as u64;
+        self.push(cmd);
+        self.wait_for_completion()
+    }
+
+    /// Discards the mapping for `(device, event)` (`DISCARD`).
+    pub fn discard(&mut self, device: DeviceId, event: EventId) -> GicResult {
+        let mut cmd = Command::new(opcode::DISCARD);
+        cmd.0[0] |= (device as u64) << 32;
+        cmd.0[1] = event as u64;
+        self.push(cmd);
+        self.wait_for_completion()
+    }
+
+    /// Invalidates any cached configuration for `(device, event)` so the ITS
+    /// re-reads the LPI configuration table (`INV`).
+    pub fn inv(&mut self, device: DeviceId, event: EventId) -> GicResult {
+        let mut cmd = Command::new(opcode::INV);
+        cmd.0[0] |= (device as u64) << 32;
+        cmd.0[1] = event as u64;
+        self.push(cmd);
+        self.wait_for_completion()
+    }
+
+    /// Polls `GITS_CREADR` until the ITS has processed every command pushed
+    /// so far, or returns [GicError::Timeout] if it never catches up.
+    fn wait_for_completion(&self) -> GicResult {
+        let target = (self.write_idx * COMMAND_SIZE) as u64;
+        for _ in 0..1_000_000 {
+            if unsafe { self.read_reg(reg::GITS_CREADR) } == target {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(GicError::Timeout)
+    }
+}
+
+/// ITS register frame offsets, GICv3 spec section 8.23.
+mod reg {
+    pub const GITS_CTLR: usize = 0x0000;
+    pub const GITS_CBASER: usize = 0x0080;
+    pub const GITS_CWRITER: usize = 0x0088;
+    pub const GITS_CREADR: usize = 0x0090;
+    pub const GITS_BASER0: usize = 0x0100;
+    pub const GICR_CTLR: usize = 0x0000;
+    pub const GICR_PROPBASER: usize = 0x0070;
+    pub const GICR_PENDBASER: usize = 0x0078;
+
+    /// `GITS_CTLR.Enabled`: the ITS does not consume anything from the
+    /// command queue until this is set; it defaults to disabled at reset.
+    pub const GITS_CTLR_ENABLED: u32 = 1 << 0;
+    /// `GICR_CTLR.EnableLPIs`: must be set before the redistributor will
+    /// deliver any LPI.
+    pub const GICR_CTLR_ENABLE_LPIS: u32 = 1 << 0;
+    /// `GICR_CTLR.RWP`: set while a `GICR_CTLR`/`GICR_PROPBASER`/
+    /// `GICR_PENDBASER` write is still being applied.
+    pub const GICR_CTLR_RWP: u32 = 1 << 3;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itt_size_field_handles_single_event_device() {
+        assert_eq!(itt_size_field(0), 0);
+        assert_eq!(itt_size_field(1), 0);
+    }
+
+    #[test]
+    fn itt_size_field_matches_bit_width() {
+        assert_eq!(itt_size_field(2), 0);
+        assert_eq!(itt_size_field(3), 1);
+        assert_eq!(itt_size_field(4), 1);
+        assert_eq!(itt_size_field(5), 2);
+        assert_eq!(itt_size_field(1024), 9);
+    }
+
+    #[test]
+    fn cbaser_value_sets_valid_bit_and_size_field() {
+        let queue = 0x1000 as *mut Command;
+        let value = cbaser_value(queue, 128);
+        assert_eq!(value & (1 << 63), 1 << 63);
+        assert_eq!(value & !(0xffu64 | 1 << 63), 0x1000);
+        assert_eq!(value & 0xff, 0); // 128 * 32 bytes = 4096 bytes = 1 page.
+    }
+
+    #[test]
+    fn cbaser_value_rounds_up_to_whole_pages() {
+        let queue = core::ptr::null_mut::<Command>();
+        let value = cbaser_value(queue, 129);
+        assert_eq!(value & 0xff, 1); // 129 * 32 bytes spans 2 pages.
+    }
+
+    #[test]
+    fn baser_value_sets_valid_bit_size_field_and_type() {
+        let table = 0x2000 as *mut u8;
+        let value = baser_value(table, 4096, baser_type::DEVICES);
+        assert_eq!(value & (1 << 63), 1 << 63);
+        assert_eq!((value >> 56) & 0b111, baser_type::DEVICES);
+        assert_eq!(value & 0xff, 0); // 4096 bytes = 1 page.
+        assert_eq!(value & !(0xffu64 | 0b111 << 56 | 1 << 63), 0x2000);
+    }
+
+    #[test]
+    fn baser_value_rounds_up_to_whole_pages() {
+        let table = core::ptr::null_mut::<u8>();
+        let value = baser_value(table, 4097, baser_type::DEVICES);
+        assert_eq!(value & 0xff, 1); // 4097 bytes spans 2 pages.
+    }
+
+    #[test]
+    fn propbaser_id_bits_field_clamps_to_architectural_minimum() {
+        // Even a single LPI must still encode the required-minimum 14 ID
+        // bits (field value 13), since 8192 (LPI_RANGE.start) alone needs 14.
+        assert_eq!(propbaser_id_bits_field(1), 13);
+        assert_eq!(propbaser_id_bits_field(0), 13);
+    }
+
+    #[test]
+    fn propbaser_id_bits_field_grows_with_lpi_count() {
+        // 8192 + 8192 - 1 = 16383, which needs 14 bits, still the minimum.
+        assert_eq!(propbaser_id_bits_field(8192), 13);
+        // 8192 + 8193 - 1 = 16384, which needs 15 bits.
+        assert_eq!(propbaser_id_bits_field(8193), 14);
+    }
+}